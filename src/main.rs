@@ -1,44 +1,53 @@
-use parse::Graph;
+use parse::{Graph, GraphParser};
 use std::str::FromStr;
 use yew::prelude::*;
 
 mod parse;
+
+// Formats a graph's debug dump, prefixed with any diagnostics, for display
+// in the output textarea. Returns the text alongside whether it's invalid.
+fn render(graph: &Graph) -> (String, bool) {
+    if graph.diagnostics().is_empty() {
+        (format!("{:#?}", graph), false)
+    } else {
+        let errors = graph
+            .diagnostics()
+            .iter()
+            .map(|d| format!("line {}, col {}: {} ({})", d.line, d.column, d.message, d.text))
+            .collect::<Vec<_>>()
+            .join("\n");
+        (format!("Error parsing graph:\n{}\n\n{:#?}", errors, graph), true)
+    }
+}
+
 #[function_component(App)]
 fn app() -> Html {
     let input_text = use_state(|| "".to_string());
     let output_text = use_state(|| "".to_string());
     let invalid = use_state(|| false);
+    // Kept alongside `input_text` so each keystroke only reparses the lines
+    // that actually changed instead of allocating a fresh `Graph`.
+    let parser = use_mut_ref(GraphParser::new);
+    let graph = use_mut_ref(|| Graph::from_str("").unwrap());
 
-    // Clone for closures
-    let input_text_clone = input_text.clone();
-    // let output_text_clone = output_text.clone();
-
-    let oninput = Callback::from(move |e: InputEvent| {
-        let input: web_sys::HtmlTextAreaElement = e.target_unchecked_into();
-        input_text_clone.set(input.value());
-    });
-
-    let invalid_clone = invalid.clone();
-    let onclick = {
+    let oninput = {
         let input_text = input_text.clone();
         let output_text = output_text.clone();
-        Callback::from(move |_| {
-            let graph_result = Graph::from_str(&input_text);
+        let invalid = invalid.clone();
+        Callback::from(move |e: InputEvent| {
+            let input: web_sys::HtmlTextAreaElement = e.target_unchecked_into();
+            let new_source = input.value();
+
+            let mut graph = graph.borrow_mut();
+            graph.reparse(&parser.borrow(), &input_text, &new_source);
+            let (debug_info, is_invalid) = render(&graph);
 
-            let debug_info = match graph_result {
-                Ok(graph) => {
-                    invalid_clone.set(false);
-                    format!("{:#?}", graph)
-                }
-                Err(err) => {
-                    invalid_clone.set(true);
-                    format!("Error parsing graph:\n{}", err)
-                }
-            };
+            input_text.set(new_source);
             output_text.set(debug_info);
+            invalid.set(is_invalid);
         })
     };
-    let aria = format!("{}", *invalid.clone());
+    let aria = format!("{}", *invalid);
     html! {
         <div class="container-fluid">
             <h3>{"Input Graph Text"}</h3>
@@ -49,8 +58,6 @@ fn app() -> Html {
                 value={(*input_text).clone()}
                 {oninput}
             />
-            <br />
-            <button {onclick}>{"Generate Graph"}</button>
             <h3>{"Output / Debug"}</h3>
             <textarea
                 aria-invalid={aria}
@@ -1,8 +1,7 @@
 use nom::{
     IResult, Parser,
-    branch::alt,
     bytes::complete::{tag, take_until},
-    character::complete::{alphanumeric1, not_line_ending, space0},
+    character::complete::{not_line_ending, space0, space1},
     combinator::opt,
     sequence::delimited,
 };
@@ -13,7 +12,10 @@ use std::str::FromStr;
 #[allow(dead_code)]
 pub struct ParsedNode {
     id: String,
-    desc: String,
+    desc: Vec<Inline>,
+    // Exact whitespace preceding `id` on its source line, preserved verbatim
+    // (rather than inferred) so the source can be faithfully reconstructed.
+    leading_ws: String,
 }
 
 #[derive(Debug)]
@@ -21,42 +23,177 @@ pub struct ParsedNode {
 pub struct ParsedEdge {
     src: String,
     dest: String,
-    directed: bool,
-    desc: String,
+    style: EdgeStyle,
+    head_src: Head,
+    head_dest: Head,
+    desc: Vec<Inline>,
+    // 0-based source line this edge currently occupies, filled in once the
+    // line number is known (parse_edge itself only sees the line's text).
+    // Used to patch the arena incrementally in `Graph::reparse`.
+    line: usize,
+    // Exact whitespace preceding `src` on its source line, preserved verbatim
+    // (rather than inferred) so the source can be faithfully reconstructed.
+    leading_ws: String,
+}
+
+// An arena slot holding a parsed node's description and the source line it
+// currently lives on. `Graph::nodes`/`Graph::edges` are `Vec<Option<_>>` so a
+// `usize` handle into them stays valid across an incremental `reparse`, even
+// once the entry it used to point at is gone.
+#[derive(Debug)]
+#[allow(dead_code)]
+struct NodeEntry {
+    desc: Vec<Inline>,
+    line: usize,
+    // Exact whitespace preceding the node's id on its source line, preserved
+    // verbatim (rather than inferred) so the source can be faithfully
+    // reconstructed. Mirrors `ParsedEdge::leading_ws`.
+    leading_ws: String,
+}
+
+impl ParsedEdge {
+    /// Derived rather than stored: an edge is directed when it points from
+    /// exactly one end (a head on the destination only), not both.
+    pub fn directed(&self) -> bool {
+        self.head_dest != Head::None && self.head_src == Head::None
+    }
+}
+
+// The line style of an edge: `--` solid, `-.-` dotted, `==` thick
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum EdgeStyle {
+    Solid,
+    Dotted,
+    Thick,
+}
+
+// The arrow head (if any) at one end of an edge
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum Head {
+    None,
+    Arrow,
+    Cross,
+    Circle,
+}
+
+// A run of inline markup recognized inside a node/edge description
+#[derive(Debug)]
+#[allow(dead_code)]
+pub enum Inline {
+    Text(String),
+    Url(String),
+    Email(String),
+    Bold(String),
+    Italic(String),
+    Code(String),
 }
 
 #[derive(Debug)]
 #[allow(dead_code)]
 pub struct Graph {
-    nodes: Vec<String>,
-    edges: Vec<ParsedEdge>,
+    id: Option<String>,
+    desc: Option<String>,
+    nodes: Vec<Option<NodeEntry>>,
+    edges: Vec<Option<ParsedEdge>>,
+    subgraphs: Vec<Graph>,
+    diagnostics: Vec<Diagnostic>,
+    // Persists across calls so `reparse` can tell which ids already have a
+    // slot and reuse it instead of growing the arena on every keystroke.
+    node_map: FxHashMap<String, usize>,
+}
+
+// A single parse failure, located so the UI can point at the offending text
+#[derive(Debug)]
+#[allow(dead_code)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub column: usize,
+    pub text: String,
+    pub message: String,
 }
 
 #[allow(dead_code)]
 pub enum ParsedLine {
     Node(ParsedNode),
     Edge(ParsedEdge),
+    BlockStart { id: String, desc: Option<String> },
+    BlockEnd,
     Blank,
     Comment(String),
     Error,
 }
 
-// Parse identifier: alphanumeric and underscore allowed
-fn parse_id(input: &str) -> IResult<&str, &str> {
-    alphanumeric1(input)
+// The bracketed tags `parse_inline` recognizes, BBCode-style: `[tag]...[/tag]`.
+// This doesn't collide with the outer node/edge-label delimiters (`id[desc]`)
+// since those are stripped off by `parse_node`/`parse_edge` before the
+// remaining text ever reaches here.
+const INLINE_TAGS: &[(&str, fn(String) -> Inline)] =
+    &[("b", Inline::Bold), ("i", Inline::Italic), ("code", Inline::Code)];
+
+// Re-parse a raw description into a tree of inline markup, auto-linkifying
+// bare URLs/emails along the way. Markup uses bracketed tags (`[b]...[/b]`,
+// `[i]...[/i]`, `` [code]...[/code] ``) rather than square-bracket labels.
+pub fn parse_inline(input: &str) -> Vec<Inline> {
+    let mut inlines = Vec::new();
+    let mut text = String::new();
+    let bytes = input.as_bytes();
+    let mut i = 0;
+    'outer: while i < input.len() {
+        let rest = &input[i..];
+        for (tag, ctor) in INLINE_TAGS {
+            let open = format!("[{tag}]");
+            let close = format!("[/{tag}]");
+            if let Some(after) = rest.strip_prefix(open.as_str()) {
+                if let Some(end) = after.find(close.as_str()) {
+                    flush_inline_text(&mut inlines, &mut text);
+                    inlines.push(ctor(after[..end].to_string()));
+                    i += open.len() + end + close.len();
+                    continue 'outer;
+                }
+            }
+        }
+        let at_word_start = i == 0 || bytes[i - 1].is_ascii_whitespace();
+        if at_word_start {
+            let word_len = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            let word = &rest[..word_len];
+            if word.starts_with("http://") || word.starts_with("https://") {
+                flush_inline_text(&mut inlines, &mut text);
+                inlines.push(Inline::Url(word.to_string()));
+                i += word_len;
+                continue;
+            }
+            if is_email(word) {
+                flush_inline_text(&mut inlines, &mut text);
+                inlines.push(Inline::Email(word.to_string()));
+                i += word_len;
+                continue;
+            }
+        }
+        let ch_len = rest.chars().next().map_or(1, char::len_utf8);
+        text.push_str(&rest[..ch_len]);
+        i += ch_len;
+    }
+    flush_inline_text(&mut inlines, &mut text);
+    inlines
+}
+
+fn flush_inline_text(inlines: &mut Vec<Inline>, text: &mut String) {
+    if !text.is_empty() {
+        inlines.push(Inline::Text(std::mem::take(text)));
+    }
 }
 
-// Parse node line: (id[node text])
-fn parse_node(input: &str) -> IResult<&str, ParsedNode> {
-    let (input, id) = parse_id(input)?;
-    let (input, desc) = delimited(tag("["), take_until("]"), tag("]")).parse(input)?;
-    Ok((
-        input,
-        ParsedNode {
-            id: id.to_string(),
-            desc: desc.to_string(),
-        },
-    ))
+// A bare `local@domain` run counts as an email: non-empty local part and a
+// domain part containing a dot that isn't leading/trailing.
+fn is_email(word: &str) -> bool {
+    match word.split_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty() && domain.contains('.') && !domain.starts_with('.') && !domain.ends_with('.')
+        }
+        None => false,
+    }
 }
 
 // Parse optional edge description: |edge|
@@ -64,100 +201,679 @@ fn parse_edge_desc(input: &str) -> IResult<&str, &str> {
     delimited(tag("|"), take_until("|"), tag("|")).parse(input)
 }
 
-// Parse edge line: (id1 --> |desc| id2) or (id1 <--> |desc| id2)
-// Edge description is optional
-fn parse_edge(input: &str) -> IResult<&str, ParsedEdge> {
-    let (input, src) = parse_id(input)?;
-    let (input, _) = space0(input)?;
-    let (input, dir) = alt((tag("-->"), tag("<-->"))).parse(input)?;
-    let directed = dir == "-->";
-    let (input, _) = space0(input)?;
-    let (input, desc) = opt(parse_edge_desc).parse(input)?;
-    let (input, _) = space0(input)?;
-    let (input, dest) = parse_id(input)?;
-    Ok((
-        input,
-        ParsedEdge {
-            src: src.to_string(),
-            dest: dest.to_string(),
-            directed,
-            desc: desc.unwrap_or("").to_string(),
-        },
-    ))
-}
-
-// Parse comment lines starting with '%%', ignores content
-fn parse_comment(input: &str) -> IResult<&str, String> {
-    let (input, _) = tag("%%")(input)?;
-    let (input, _) = not_line_ending(input)?;
-    Ok((input, input.to_string()))
-}
-
-// Parse a single line as either node, edge, comment, or empty line
-fn parse_line(input: &str) -> IResult<&str, ParsedLine> {
-    let (input, _) = space0(input)?;
-
-    // Try parse empty
-    if input.is_empty() {
-        return Ok((input, ParsedLine::Blank));
-    }
-    // Try parse comment
-    if let Ok((input, comment)) = parse_comment(input) {
-        return Ok((input, ParsedLine::Comment(comment)));
-    }
-    // Try parse node
-    if let Ok((input, node)) = parse_node(input) {
-        return Ok((input, ParsedLine::Node(node)));
-    }
-    // Try parse edge
-    if let Ok((input, edge)) = parse_edge(input) {
-        return Ok((input, ParsedLine::Edge(edge)));
-    }
-    // If line is blank or cannot parse, skip
-    Ok((input, ParsedLine::Error))
-}
-
-// Parse the entire input text into graph with nodes and edges
-fn parse_graph(input: &str) -> Result<Graph, String> {
-    let mut nodes = Vec::new();
-    let mut edges = Vec::new();
-    let mut node_map = FxHashMap::default();
-    for line in input.lines() {
-        match parse_line(line) {
-            Ok((input, result)) => match result {
+// Parse a node's `[...]` label. Captures up to the *last* `]` on the line
+// (lines are the unit `parse_line` works on, so there's never a second
+// label to worry about running into) rather than the first, so a bracketed
+// inline tag like `[b]...[/b]` inside the label doesn't get mistaken for
+// the label's own closing bracket.
+fn parse_node_label(input: &str) -> IResult<&str, &str> {
+    let (input, _) = tag("[")(input)?;
+    match input.rfind(']') {
+        Some(end) => Ok((&input[end + 1..], &input[..end])),
+        None => Err(nom::Err::Error(nom::error::Error::new(input, nom::error::ErrorKind::Tag))),
+    }
+}
+
+// Picks whichever of `current` and the failing parser's remainder is
+// shorter, i.e. whichever reflects more input consumed before failing.
+// `nom::Err::Incomplete` carries no position, so it leaves `current` as-is.
+fn furthest_remaining<'b>(current: &'b str, err: &nom::Err<nom::error::Error<&'b str>>) -> &'b str {
+    match err {
+        nom::Err::Error(e) | nom::Err::Failure(e) if e.input.len() < current.len() => e.input,
+        _ => current,
+    }
+}
+
+// Look up whether `id` is already known in the current scope or any enclosing one
+fn resolve_in_scopes(
+    id: &str,
+    node_map: &FxHashMap<String, usize>,
+    parent_scopes: &[FxHashMap<String, usize>],
+) -> bool {
+    node_map.contains_key(id) || parent_scopes.iter().any(|scope| scope.contains_key(id))
+}
+
+/// Configures the grammar `Graph::from_str` parses against, builder-style:
+///
+/// ```ignore
+/// GraphParser::new()
+///     .comment_prefix("%%")
+///     .allow_unicode_ids(true)
+///     .strict(true)
+///     .edge_styles(&[("--", EdgeStyle::Solid)]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct GraphParser {
+    comment_prefix: String,
+    allow_unicode_ids: bool,
+    strict: bool,
+    edge_styles: Vec<(String, EdgeStyle)>,
+}
+
+impl Default for GraphParser {
+    fn default() -> Self {
+        Self {
+            comment_prefix: "%%".to_string(),
+            allow_unicode_ids: true,
+            strict: true,
+            edge_styles: vec![
+                ("--".to_string(), EdgeStyle::Solid),
+                ("-.-".to_string(), EdgeStyle::Dotted),
+                ("==".to_string(), EdgeStyle::Thick),
+            ],
+        }
+    }
+}
+
+impl GraphParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the marker that begins a comment line. Defaults to `%%`.
+    pub fn comment_prefix(mut self, prefix: &str) -> Self {
+        self.comment_prefix = prefix.to_string();
+        self
+    }
+
+    /// Whether `parse_id` accepts Unicode alphanumerics in addition to ASCII.
+    /// Defaults to `true`.
+    pub fn allow_unicode_ids(mut self, allow: bool) -> Self {
+        self.allow_unicode_ids = allow;
+        self
+    }
+
+    /// Whether an unparseable line is a hard error (recorded as a
+    /// [`Diagnostic`]) or silently skipped. Defaults to `true`.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Sets the line-style tokens `parse_edge` recognizes between the
+    /// (optional) arrow heads, e.g. `&[("--", EdgeStyle::Solid)]`.
+    pub fn edge_styles(mut self, styles: &[(&str, EdgeStyle)]) -> Self {
+        self.edge_styles = styles.iter().map(|(s, style)| (s.to_string(), *style)).collect();
+        self
+    }
+
+    // Parse identifier: alphanumeric (ASCII, or Unicode when configured) and
+    // underscore allowed. Walks `char_indices` rather than a byte offset so
+    // multi-byte characters are never split mid-codepoint.
+    fn parse_id<'b>(&self, input: &'b str) -> IResult<&'b str, &'b str> {
+        let is_id_char = |c: char| {
+            if self.allow_unicode_ids {
+                c.is_alphanumeric() || c == '_'
+            } else {
+                c.is_ascii_alphanumeric() || c == '_'
+            }
+        };
+        let end = input
+            .char_indices()
+            .find(|(_, c)| !is_id_char(*c))
+            .map_or(input.len(), |(i, _)| i);
+        if end == 0 {
+            return Err(nom::Err::Error(nom::error::Error::new(
+                input,
+                nom::error::ErrorKind::AlphaNumeric,
+            )));
+        }
+        Ok((&input[end..], &input[..end]))
+    }
+
+    // Parse node line: (id[node text])
+    fn parse_node<'b>(&self, input: &'b str) -> IResult<&'b str, ParsedNode> {
+        let (input, id) = self.parse_id(input)?;
+        let (input, desc) = parse_node_label(input)?;
+        Ok((
+            input,
+            ParsedNode {
+                id: id.to_string(),
+                desc: parse_inline(desc),
+                leading_ws: String::new(),
+            },
+        ))
+    }
+
+    // Parse an optional head character, e.g. the `<`/`x`/`o` before a line or
+    // the `>`/`x`/`o` after one. Falls through to `Head::None` rather than failing.
+    fn parse_head<'b>(&self, input: &'b str, head_chars: &[(char, Head)]) -> IResult<&'b str, Head> {
+        for (ch, head) in head_chars {
+            if let Some(rest) = input.strip_prefix(*ch) {
+                return Ok((rest, *head));
+            }
+        }
+        Ok((input, Head::None))
+    }
+
+    // Parse one of the configured line-style tokens between the arrow heads
+    fn parse_edge_style<'b>(&self, input: &'b str) -> IResult<&'b str, EdgeStyle> {
+        for (token, style) in &self.edge_styles {
+            if let Some(rest) = input.strip_prefix(token.as_str()) {
+                return Ok((rest, *style));
+            }
+        }
+        Err(nom::Err::Error(nom::error::Error::new(
+            input,
+            nom::error::ErrorKind::Tag,
+        )))
+    }
+
+    // Parse a full arrow: optional left head, a line-style run, optional right
+    // head, e.g. `-->`, `<-->`, `x-.-o`, `==>`. At most one head character
+    // is read on each side, so in `x-.->o` the arrow ends at `>` and the `o`
+    // is left unconsumed.
+    fn parse_arrow<'b>(&self, input: &'b str) -> IResult<&'b str, (Head, EdgeStyle, Head)> {
+        let (input, head_src) = self.parse_head(input, &[('<', Head::Arrow), ('x', Head::Cross), ('o', Head::Circle)])?;
+        let (input, style) = self.parse_edge_style(input)?;
+        let (input, head_dest) = self.parse_head(input, &[('>', Head::Arrow), ('x', Head::Cross), ('o', Head::Circle)])?;
+        Ok((input, (head_src, style, head_dest)))
+    }
+
+    // Parse edge line: (id1 --> |desc| id2), (id1 <--> |desc| id2), or any
+    // other combination of line style and head type, e.g. `id1 x-.-o id2`.
+    // Edge description is optional
+    fn parse_edge<'b>(&self, input: &'b str) -> IResult<&'b str, ParsedEdge> {
+        let (input, src) = self.parse_id(input)?;
+        let (input, _) = space0(input)?;
+        let (input, (head_src, style, head_dest)) = self.parse_arrow(input)?;
+        let (input, _) = space0(input)?;
+        let (input, desc) = opt(parse_edge_desc).parse(input)?;
+        let (input, _) = space0(input)?;
+        let (input, dest) = self.parse_id(input)?;
+        let (input, _) = space0(input)?;
+        if !input.is_empty() {
+            // Leftover content after the destination id means the line is
+            // not a single `src arrow dest` edge (e.g. in `a x-.->o b` the
+            // arrow ends at `>`, the stray `o` after it is read as the
+            // destination id and `b` is left over), so `dest` would bind to
+            // the wrong id. Reject rather than silently mis-parsing.
+            return Err(nom::Err::Error(nom::error::Error::new(input, nom::error::ErrorKind::Tag)));
+        }
+        Ok((
+            input,
+            ParsedEdge {
+                src: src.to_string(),
+                dest: dest.to_string(),
+                style,
+                head_src,
+                head_dest,
+                desc: parse_inline(desc.unwrap_or("")),
+                // Filled in by the caller, which knows the line number.
+                line: 0,
+                leading_ws: String::new(),
+            },
+        ))
+    }
+
+    // Parse a subgraph/container opener: `subgraph id [label]`, label optional
+    fn parse_block_start<'b>(&self, input: &'b str) -> IResult<&'b str, (&'b str, Option<&'b str>)> {
+        let (input, _) = tag("subgraph")(input)?;
+        let (input, _) = space1(input)?;
+        let (input, id) = self.parse_id(input)?;
+        let (input, _) = space0(input)?;
+        let (input, desc) = opt(delimited(tag("["), take_until("]"), tag("]"))).parse(input)?;
+        Ok((input, (id, desc)))
+    }
+
+    // Parse the matching `end` that closes a subgraph block. Requires the
+    // token to consume the rest of the line (trailing whitespace aside) so
+    // an id that merely starts with `end` (e.g. `endpoint[Done]`) is parsed
+    // as a node instead of being swallowed as a block terminator.
+    fn parse_block_end<'b>(&self, input: &'b str) -> IResult<&'b str, &'b str> {
+        let (input, out) = tag("end")(input)?;
+        let (input, _) = space0(input)?;
+        if !input.is_empty() {
+            return Err(nom::Err::Error(nom::error::Error::new(input, nom::error::ErrorKind::Tag)));
+        }
+        Ok((input, out))
+    }
+
+    // Parse comment lines, ignores content
+    fn parse_comment<'b>(&self, input: &'b str) -> IResult<&'b str, String> {
+        let (input, _) = tag(self.comment_prefix.as_str())(input)?;
+        let (input, _) = not_line_ending(input)?;
+        Ok((input, input.to_string()))
+    }
+
+    // Parse a single line as either node, edge, block marker, comment, or empty line
+    fn parse_line<'b>(&self, input: &'b str) -> IResult<&'b str, ParsedLine> {
+        // Kept separately (rather than discarded) so it can be stamped onto
+        // whichever `ParsedNode`/`ParsedEdge` the rest of the line yields.
+        let (input, leading_ws) = space0(input)?;
+
+        // Try parse empty
+        if input.is_empty() {
+            return Ok((input, ParsedLine::Blank));
+        }
+
+        // None of the alternatives below ever actually fail this function
+        // (every line becomes *some* `ParsedLine`, `Error` included), but
+        // each alternative can still fail partway through. `furthest` tracks
+        // whichever attempt consumed the most before giving up, so that if
+        // every alternative fails, the `Error` we return points at the
+        // actual divergence point rather than just past the leading
+        // whitespace.
+        let mut furthest = input;
+
+        // Try parse comment
+        match self.parse_comment(input) {
+            Ok((input, comment)) => return Ok((input, ParsedLine::Comment(comment))),
+            Err(e) => furthest = furthest_remaining(furthest, &e),
+        }
+        // Try parse block start/end
+        match self.parse_block_start(input) {
+            Ok((input, (id, desc))) => {
+                return Ok((
+                    input,
+                    ParsedLine::BlockStart {
+                        id: id.to_string(),
+                        desc: desc.map(str::to_string),
+                    },
+                ));
+            }
+            Err(e) => furthest = furthest_remaining(furthest, &e),
+        }
+        match self.parse_block_end(input) {
+            Ok((input, _)) => return Ok((input, ParsedLine::BlockEnd)),
+            Err(e) => furthest = furthest_remaining(furthest, &e),
+        }
+        // Try parse node
+        match self.parse_node(input) {
+            Ok((input, mut node)) => {
+                node.leading_ws = leading_ws.to_string();
+                return Ok((input, ParsedLine::Node(node)));
+            }
+            Err(e) => furthest = furthest_remaining(furthest, &e),
+        }
+        // Try parse edge
+        match self.parse_edge(input) {
+            Ok((input, mut edge)) => {
+                edge.leading_ws = leading_ws.to_string();
+                return Ok((input, ParsedLine::Edge(edge)));
+            }
+            Err(e) => furthest = furthest_remaining(furthest, &e),
+        }
+        // Every alternative failed: report where the best attempt diverged.
+        Ok((furthest, ParsedLine::Error))
+    }
+
+    // Parse the body of a graph (or nested subgraph block) line-by-line until
+    // the input runs out or a matching `end` closes the current block. In
+    // strict mode, bad lines never abort the parse either: they're recorded
+    // as diagnostics and parsing continues with the next line; in lenient
+    // mode they're silently skipped.
+    fn parse_graph_body<'a, I: Iterator<Item = &'a str>>(
+        &self,
+        lines: &mut I,
+        id: Option<String>,
+        desc: Option<String>,
+        parent_scopes: &mut Vec<FxHashMap<String, usize>>,
+        line_no: &mut usize,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Graph {
+        let mut nodes = Vec::new();
+        let mut edges = Vec::new();
+        let mut subgraphs = Vec::new();
+        let mut node_map = FxHashMap::default();
+        while let Some(line) = lines.next() {
+            *line_no += 1;
+            let line_idx = *line_no - 1;
+            // `parse_line` never fails: a line that matches nothing still
+            // comes back as `ParsedLine::Error`.
+            let (remaining, result) = self.parse_line(line).expect("parse_line never fails");
+            match result {
                 ParsedLine::Node(node) => {
-                    if let Some(id) = node_map.get(&node.id) {
-                        let id = *id;
-                        nodes[id] = node.id.clone();
+                    // A repeated id re-declares the same node: update
+                    // the slot `node_map` already points at with the
+                    // new description rather than growing the arena
+                    // with a second, unreferenced entry.
+                    if let Some(&idx) = node_map.get(&node.id) {
+                        nodes[idx] = Some(NodeEntry {
+                            desc: node.desc,
+                            line: line_idx,
+                            leading_ws: node.leading_ws,
+                        });
                     } else {
                         node_map.insert(node.id.clone(), nodes.len());
+                        nodes.push(Some(NodeEntry {
+                            desc: node.desc,
+                            line: line_idx,
+                            leading_ws: node.leading_ws,
+                        }));
                     }
-                    nodes.push(node.desc);
                 }
-                ParsedLine::Edge(edge) => {
-                    if !node_map.contains_key(&edge.src) {
+                ParsedLine::Edge(mut edge) => {
+                    if !resolve_in_scopes(&edge.src, &node_map, parent_scopes) {
                         node_map.insert(edge.src.clone(), nodes.len());
+                        nodes.push(None);
                     }
-                    if !node_map.contains_key(&edge.dest) {
+                    if !resolve_in_scopes(&edge.dest, &node_map, parent_scopes) {
                         node_map.insert(edge.dest.clone(), nodes.len());
+                        nodes.push(None);
                     }
-                    edges.push(edge)
+                    edge.line = line_idx;
+                    edges.push(Some(edge))
+                }
+                ParsedLine::BlockStart { id, desc } => {
+                    parent_scopes.push(node_map.clone());
+                    let subgraph = self.parse_graph_body(
+                        lines,
+                        Some(id),
+                        desc,
+                        parent_scopes,
+                        line_no,
+                        diagnostics,
+                    );
+                    parent_scopes.pop();
+                    subgraphs.push(subgraph);
+                }
+                ParsedLine::BlockEnd if id.is_some() => {
+                    return Graph {
+                        id,
+                        desc,
+                        nodes,
+                        edges,
+                        subgraphs,
+                        diagnostics: Vec::new(),
+                        node_map,
+                    };
                 }
+                // No block is open at this level (we're at the top of
+                // the document), so a bare `end` here doesn't close
+                // anything: record it as a diagnostic rather than
+                // truncating the rest of the parse.
+                ParsedLine::BlockEnd if self.strict => {
+                    diagnostics.push(Diagnostic {
+                        line: *line_no,
+                        column: 0,
+                        text: line.to_string(),
+                        message: "unmatched 'end' with no open block".to_string(),
+                    });
+                }
+                ParsedLine::BlockEnd => (),
                 ParsedLine::Blank | ParsedLine::Comment(_) => (),
-                ParsedLine::Error => {
-                    return Err(format!("Failed to parse line '{}'", input));
+                ParsedLine::Error if self.strict => {
+                    // The byte offset where parsing diverged, measured between the
+                    // original line and the unconsumed remainder nom handed back.
+                    let column = remaining.as_ptr() as usize - line.as_ptr() as usize;
+                    diagnostics.push(Diagnostic {
+                        line: *line_no,
+                        column,
+                        text: line.to_string(),
+                        message: format!("failed to parse line '{}'", line),
+                    });
                 }
-            },
-            Err(e) => return Err(format!("Failed to parse line '{}': {:?}", line, e)),
+                ParsedLine::Error => (),
+            }
+        }
+        Graph {
+            id,
+            desc,
+            nodes,
+            edges,
+            subgraphs,
+            diagnostics: Vec::new(),
+            node_map,
+        }
+    }
+
+    /// Parses the entire input text into a graph with nodes, edges, and
+    /// nested subgraphs, collecting every bad line as a diagnostic instead
+    /// of bailing out on the first one (unless `strict(false)` was set).
+    pub fn parse(&self, input: &str) -> Graph {
+        let mut lines = input.lines();
+        let mut parent_scopes = Vec::new();
+        let mut line_no = 0;
+        let mut diagnostics = Vec::new();
+        let mut graph = self.parse_graph_body(
+            &mut lines,
+            None,
+            None,
+            &mut parent_scopes,
+            &mut line_no,
+            &mut diagnostics,
+        );
+        graph.diagnostics = diagnostics;
+        graph
+    }
+}
+
+impl Graph {
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Incrementally updates this graph for an edit from `old_source` to
+    /// `new_source`: only the line span that actually changed is re-parsed,
+    /// so node/edge arena handles outside that span stay valid. Falls back
+    /// to a full `parser.parse(new_source)` whenever this graph has
+    /// subgraphs, or the edit introduces/removes one — patching across
+    /// arbitrary nested blocks isn't supported.
+    pub fn reparse(&mut self, parser: &GraphParser, old_source: &str, new_source: &str) {
+        if !self.subgraphs.is_empty() {
+            *self = parser.parse(new_source);
+            return;
+        }
+
+        let old_lines: Vec<&str> = old_source.lines().collect();
+        let new_lines: Vec<&str> = new_source.lines().collect();
+        let prefix = old_lines
+            .iter()
+            .zip(new_lines.iter())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let suffix = old_lines[prefix..]
+            .iter()
+            .rev()
+            .zip(new_lines[prefix..].iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let old_end = old_lines.len() - suffix;
+        let new_end = new_lines.len() - suffix;
+        let delta = new_end as isize - old_end as isize;
+
+        // Evict arena slots whose declaration fell inside the changed range,
+        // and slide the ones after it to their new line numbers.
+        for slot in self.nodes.iter_mut() {
+            if let Some(entry) = slot {
+                if entry.line >= prefix && entry.line < old_end {
+                    *slot = None;
+                } else if entry.line >= old_end {
+                    entry.line = (entry.line as isize + delta) as usize;
+                }
+            }
+        }
+        for slot in self.edges.iter_mut() {
+            if let Some(edge) = slot {
+                if edge.line >= prefix && edge.line < old_end {
+                    *slot = None;
+                } else if edge.line >= old_end {
+                    edge.line = (edge.line as isize + delta) as usize;
+                }
+            }
+        }
+        self.node_map
+            .retain(|_, idx| self.nodes.get(*idx).is_some_and(Option::is_some));
+        self.diagnostics.retain_mut(|d| {
+            if d.line <= prefix {
+                true
+            } else if d.line > old_end {
+                d.line = (d.line as isize + delta) as usize;
+                true
+            } else {
+                false
+            }
+        });
+
+        // Re-parse just the changed lines and splice them back into the arena.
+        for (offset, line) in new_lines[prefix..new_end].iter().enumerate() {
+            let line_idx = prefix + offset;
+            // `parse_line` never fails: a line that matches nothing still
+            // comes back as `ParsedLine::Error`.
+            match parser.parse_line(line).expect("parse_line never fails") {
+                (_, ParsedLine::BlockStart { .. } | ParsedLine::BlockEnd) => {
+                    // A subgraph marker showed up inside a previously flat
+                    // graph: give up patching and reparse the whole thing.
+                    *self = parser.parse(new_source);
+                    return;
+                }
+                (_, ParsedLine::Node(node)) => {
+                    // Mirrors `parse_graph_body`: a repeated id updates the
+                    // slot `node_map` already points at instead of growing
+                    // the arena with a second, unreferenced entry.
+                    if let Some(&idx) = self.node_map.get(&node.id) {
+                        self.nodes[idx] = Some(NodeEntry {
+                            desc: node.desc,
+                            line: line_idx,
+                            leading_ws: node.leading_ws,
+                        });
+                    } else {
+                        self.node_map.insert(node.id.clone(), self.nodes.len());
+                        self.nodes.push(Some(NodeEntry {
+                            desc: node.desc,
+                            line: line_idx,
+                            leading_ws: node.leading_ws,
+                        }));
+                    }
+                }
+                (_, ParsedLine::Edge(mut edge)) => {
+                    // Mirrors the seeding in `parse_graph_body`: an edge's
+                    // endpoints get an implicit slot in `node_map` the first
+                    // time they're seen, so ids that only ever appear on
+                    // edges still resolve (there are no parent scopes to
+                    // check here, since `reparse` only patches flat graphs).
+                    if !self.node_map.contains_key(&edge.src) {
+                        self.node_map.insert(edge.src.clone(), self.nodes.len());
+                        self.nodes.push(None);
+                    }
+                    if !self.node_map.contains_key(&edge.dest) {
+                        self.node_map.insert(edge.dest.clone(), self.nodes.len());
+                        self.nodes.push(None);
+                    }
+                    edge.line = line_idx;
+                    self.edges.push(Some(edge));
+                }
+                (_, ParsedLine::Blank | ParsedLine::Comment(_)) => (),
+                (remaining, ParsedLine::Error) if parser.strict => {
+                    let column = remaining.as_ptr() as usize - line.as_ptr() as usize;
+                    self.diagnostics.push(Diagnostic {
+                        line: line_idx + 1,
+                        column,
+                        text: line.to_string(),
+                        message: format!("failed to parse line '{}'", line),
+                    });
+                }
+                (_, ParsedLine::Error) => (),
+            }
         }
     }
-    Ok(Graph { nodes, edges })
 }
 
 impl FromStr for Graph {
-    type Err = String;
+    type Err = std::convert::Infallible;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        parse_graph(s)
+        Ok(GraphParser::new().parse(s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Sorted (id, line) pairs for every live node, so two graphs can be
+    // compared regardless of arena slot order.
+    fn node_ids(graph: &Graph) -> Vec<(String, usize)> {
+        let mut ids: Vec<_> = graph
+            .node_map
+            .iter()
+            .filter_map(|(id, &idx)| graph.nodes[idx].as_ref().map(|e| (id.clone(), e.line)))
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn subgraph_nesting_parses_a_nested_graph() {
+        let graph = GraphParser::new().parse("subgraph g1 [Group]\na[A]\nend\nb[B]");
+        assert_eq!(graph.subgraphs.len(), 1);
+        assert_eq!(graph.subgraphs[0].id.as_deref(), Some("g1"));
+        assert!(graph.subgraphs[0].node_map.contains_key("a"));
+        assert!(graph.node_map.contains_key("b"));
+    }
+
+    #[test]
+    fn unmatched_end_at_top_level_is_a_diagnostic_not_a_truncation() {
+        let graph = GraphParser::new().parse("a[A]\nend\nb[B]");
+        assert!(graph.node_map.contains_key("a"));
+        assert!(graph.node_map.contains_key("b"));
+        assert!(graph.diagnostics().iter().any(|d| d.message.contains("unmatched")));
+    }
+
+    #[test]
+    fn node_label_keeps_inline_tag_despite_internal_bracket() {
+        let graph = GraphParser::new().parse("n[a [b]x[/b] c]");
+        let idx = graph.node_map["n"];
+        let desc = &graph.nodes[idx].as_ref().unwrap().desc;
+        assert!(desc.iter().any(|i| matches!(i, Inline::Bold(s) if s == "x")));
+    }
+
+    #[test]
+    fn parse_inline_recognizes_tags_urls_and_emails() {
+        let inlines = parse_inline("see [b]bold[/b], https://example.com and a@b.com");
+        assert!(inlines.iter().any(|i| matches!(i, Inline::Bold(s) if s == "bold")));
+        assert!(inlines.iter().any(|i| matches!(i, Inline::Url(s) if s == "https://example.com")));
+        assert!(inlines.iter().any(|i| matches!(i, Inline::Email(s) if s == "a@b.com")));
+    }
+
+    #[test]
+    fn parse_arrow_variants_produce_the_expected_style_and_heads() {
+        let graph = GraphParser::new().parse("a x-.->b");
+        let edge = graph.edges[0].as_ref().unwrap();
+        assert_eq!(edge.head_src, Head::Cross);
+        assert_eq!(edge.style, EdgeStyle::Dotted);
+        assert_eq!(edge.head_dest, Head::Arrow);
+        // Both ends carry a head (cross src, arrow dest), so this isn't
+        // "directed" under `ParsedEdge::directed`'s definition (exactly one
+        // head, on the destination only).
+        assert!(!edge.directed());
+    }
+
+    #[test]
+    fn stray_head_char_after_arrow_is_rejected_not_misbound() {
+        let graph = GraphParser::new().parse("a x-.->o b");
+        assert!(graph.edges.is_empty());
+        assert!(!graph.diagnostics().is_empty());
+    }
+
+    #[test]
+    fn duplicate_node_id_keeps_the_real_description_in_the_shared_slot() {
+        let graph = GraphParser::new().parse("a[First]\na[Second]\n");
+        assert_eq!(graph.nodes.len(), 1);
+        let desc = &graph.nodes[0].as_ref().unwrap().desc;
+        assert!(desc.iter().any(|i| matches!(i, Inline::Text(s) if s == "Second")));
+    }
+
+    #[test]
+    fn reparse_matches_a_fresh_parse_after_inserting_a_line() {
+        let parser = GraphParser::new();
+        let old_source = "a[A]\nb[B]\na-->b";
+        let mut graph = parser.parse(old_source);
+        let new_source = "a[A]\nc[C]\nb[B]\na-->b";
+        graph.reparse(&parser, old_source, new_source);
+        assert_eq!(node_ids(&graph), node_ids(&parser.parse(new_source)));
+    }
+
+    #[test]
+    fn reparse_shifts_trailing_diagnostic_line_numbers() {
+        let parser = GraphParser::new();
+        let old_source = "a[A]\nnot a valid line\nb[B]";
+        let mut graph = parser.parse(old_source);
+        assert_eq!(graph.diagnostics()[0].line, 2);
+
+        let new_source = "a[A]\nextra[Extra]\nnot a valid line\nb[B]";
+        graph.reparse(&parser, old_source, new_source);
+        assert_eq!(graph.diagnostics()[0].line, 3);
     }
 }